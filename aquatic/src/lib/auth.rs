@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::common::Time;
+
+/// A per-client secret passkey, required to announce or scrape while the
+/// tracker runs in [`crate::handlers::TrackerMode::Private`].
+///
+/// The UDP protocol has no field for this, so it isn't carried on
+/// `AnnounceRequest`/`ScrapeRequest` themselves: it's expected to be
+/// extracted upstream of the request workers, either from a dedicated
+/// path component on the bound socket or from a protocol extension
+/// field, and handed to the handlers alongside the usual
+/// `(request, socket_addr)` pair.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PasskeyId(pub String);
+
+/// In-memory store of currently valid passkeys, with optional expiry.
+///
+/// [`update_from_path`](Self::update_from_path) is meant to be called by
+/// whatever periodic maintenance task the embedding binary already runs
+/// for connection/access-list cleanup, on the same cadence, so revoking
+/// a key takes effect within one cleanup interval. This crate doesn't
+/// define that task itself.
+#[derive(Default)]
+pub struct PasskeyStore {
+    keys: HashMap<PasskeyId, Option<Time>>,
+}
+
+impl PasskeyStore {
+    pub fn is_valid(&self, key: &Option<PasskeyId>) -> bool {
+        let key = match key {
+            Some(key) => key,
+            None => return false,
+        };
+
+        match self.keys.get(key) {
+            Some(Some(expiry)) => expiry.0 > Instant::now(),
+            Some(None) => true,
+            None => false,
+        }
+    }
+
+    /// Reload the key set from `path`.
+    ///
+    /// Expects one key per line, optionally followed by a comma and the
+    /// Unix timestamp in seconds at which the key expires (e.g.
+    /// `abc123...,1700000000`). Keys without an expiry never expire.
+    pub fn update_from_path(&mut self, path: &Path) -> ::std::io::Result<()> {
+        let contents = fs::read_to_string(path)?;
+        let now = Instant::now();
+        let now_unix_secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let mut keys = HashMap::new();
+
+        for line in contents.lines() {
+            let line = line.trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut parts = line.splitn(2, ',');
+
+            let key = PasskeyId(parts.next().unwrap().trim().to_string());
+            let expiry = parts
+                .next()
+                .and_then(|secs| secs.trim().parse::<u64>().ok())
+                .map(|expires_at| {
+                    Time(now + Duration::from_secs(expires_at.saturating_sub(now_unix_secs)))
+                });
+
+            keys.insert(key, expiry);
+        }
+
+        self.keys = keys;
+
+        Ok(())
+    }
+}
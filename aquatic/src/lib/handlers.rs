@@ -9,19 +9,41 @@ use rand::{SeedableRng, Rng, rngs::{SmallRng, StdRng}};
 
 use bittorrent_udp::types::*;
 
+use crate::auth::PasskeyId;
 use crate::common::*;
 use crate::config::Config;
 
 
+/// Tracker operating mode, mirroring the Static/Dynamic/Private split
+/// exposed by udpt.
+///
+/// Controls how `handle_announce_requests` reacts to info hashes that
+/// aren't already in `data.torrents`, and whether
+/// `handle_announce_requests` and `handle_scrape_requests` require a
+/// passkey before touching `data.torrents`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrackerMode {
+    /// Only track info hashes that were added ahead of time (e.g. by an
+    /// admin tool). Announces for unknown info hashes are rejected.
+    Static,
+    /// Track any info hash on first announce. This is the original,
+    /// pre-existing behavior.
+    Dynamic,
+    /// Like Dynamic, but announces and scrapes must carry a valid
+    /// passkey before any swarm mutation is allowed.
+    Private,
+}
+
+
 pub fn run_request_worker(
     state: State,
     config: Config,
-    request_receiver: Receiver<(Request, SocketAddr)>,
+    request_receiver: Receiver<(Request, SocketAddr, Option<PasskeyId>)>,
     response_sender: Sender<(Response, SocketAddr)>,
 ){
     let mut connect_requests: Vec<(ConnectRequest, SocketAddr)> = Vec::new();
-    let mut announce_requests: Vec<(AnnounceRequest, SocketAddr)> = Vec::new();
-    let mut scrape_requests: Vec<(ScrapeRequest, SocketAddr)> = Vec::new();
+    let mut announce_requests: Vec<(AnnounceRequest, SocketAddr, Option<PasskeyId>)> = Vec::new();
+    let mut scrape_requests: Vec<(ScrapeRequest, SocketAddr, Option<PasskeyId>)> = Vec::new();
 
     let mut responses: Vec<(Response, SocketAddr)> = Vec::new();
 
@@ -41,7 +63,7 @@ pub fn run_request_worker(
         // number is reached if having waited for too long for a request, but
         // only if HandlerData mutex isn't locked.
         for i in 0..config.handlers.max_requests_per_iter {
-            let (request, src): (Request, SocketAddr) = if i == 0 {
+            let (request, src, key): (Request, SocketAddr, Option<PasskeyId>) = if i == 0 {
                 match request_receiver.recv(){
                     Ok(r) => r,
                     Err(_) => break, // Really shouldn't happen
@@ -66,10 +88,10 @@ pub fn run_request_worker(
                     connect_requests.push((r, src))
                 },
                 Request::Announce(r) => {
-                    announce_requests.push((r, src))
+                    announce_requests.push((r, src, key))
                 },
                 Request::Scrape(r) => {
-                    scrape_requests.push((r, src))
+                    scrape_requests.push((r, src, key))
                 },
             }
         }
@@ -94,6 +116,7 @@ pub fn run_request_worker(
         );
         handle_scrape_requests(
             &mut data,
+            &config,
             scrape_requests.drain(..),
             &mut responses
         );
@@ -145,10 +168,10 @@ pub fn handle_announce_requests(
     data: &mut MutexGuard<HandlerData>,
     config: &Config,
     rng: &mut SmallRng,
-    requests: Drain<(AnnounceRequest, SocketAddr)>,
+    requests: Drain<(AnnounceRequest, SocketAddr, Option<PasskeyId>)>,
     responses: &mut Vec<(Response, SocketAddr)>,
 ){
-    responses.extend(requests.map(|(request, src)| {
+    responses.extend(requests.map(|(request, src, key)| {
         let connection_key = ConnectionKey {
             connection_id: request.connection_id,
             socket_addr: src,
@@ -163,6 +186,34 @@ pub fn handle_announce_requests(
             return (response.into(), src);
         }
 
+        if config.mode == TrackerMode::Private && !data.passkeys.is_valid(&key) {
+            let response = ErrorResponse {
+                transaction_id: request.transaction_id,
+                message: "unauthorized".to_string()
+            };
+
+            return (response.into(), src);
+        }
+
+        let torrent_data = match config.mode {
+            TrackerMode::Static => {
+                match data.torrents.get_mut(&request.info_hash) {
+                    Some(torrent_data) => torrent_data,
+                    None => {
+                        let response = ErrorResponse {
+                            transaction_id: request.transaction_id,
+                            message: "torrent not tracked".to_string()
+                        };
+
+                        return (response.into(), src);
+                    }
+                }
+            },
+            TrackerMode::Dynamic | TrackerMode::Private => {
+                data.torrents.entry(request.info_hash).or_default()
+            },
+        };
+
         let peer_key = PeerMapKey {
             ip: src.ip(),
             peer_id: request.peer_id,
@@ -171,10 +222,6 @@ pub fn handle_announce_requests(
         let peer = Peer::from_announce_and_ip(&request, src.ip());
         let peer_status = peer.status;
 
-        let torrent_data = data.torrents
-            .entry(request.info_hash)
-            .or_default();
-        
         let opt_removed_peer_status = if peer_status == PeerStatus::Stopped {
             torrent_data.peers.remove(&peer_key)
                 .map(|peer| peer.status)
@@ -183,6 +230,10 @@ pub fn handle_announce_requests(
                 .map(|peer| peer.status)
         };
 
+        if counts_as_new_download(request.event, opt_removed_peer_status) {
+            torrent_data.num_downloads.fetch_add(1, Ordering::SeqCst);
+        }
+
         let max_num_peers_to_take = (request.peers_wanted.0.max(0) as usize)
             .min(config.network.max_response_peers);
 
@@ -225,15 +276,30 @@ pub fn handle_announce_requests(
 }
 
 
+/// The UDP protocol practically limits a single scrape request to about
+/// 74 info hashes anyway (packet size), but don't rely on clients
+/// behaving: truncate up front so a request claiming far more than that
+/// can't force an oversized allocation in `handle_scrape_requests`.
+#[inline]
+fn truncate_scrape_info_hashes(info_hashes: &[InfoHash], max_scrape_torrents: usize) -> &[InfoHash] {
+    if info_hashes.len() > max_scrape_torrents {
+        &info_hashes[..max_scrape_torrents]
+    } else {
+        info_hashes
+    }
+}
+
+
 #[inline]
 pub fn handle_scrape_requests(
     data: &mut MutexGuard<HandlerData>,
-    requests: Drain<(ScrapeRequest, SocketAddr)>,
+    config: &Config,
+    requests: Drain<(ScrapeRequest, SocketAddr, Option<PasskeyId>)>,
     responses: &mut Vec<(Response, SocketAddr)>,
 ){
-    let empty_stats = create_torrent_scrape_statistics(0, 0);
+    let empty_stats = create_torrent_scrape_statistics(0, 0, 0);
 
-    responses.extend(requests.map(|(request, src)|{
+    responses.extend(requests.map(|(request, src, key)|{
         let connection_key = ConnectionKey {
             connection_id: request.connection_id,
             socket_addr: src,
@@ -248,15 +314,30 @@ pub fn handle_scrape_requests(
             return (response.into(), src);
         }
 
+        if config.mode == TrackerMode::Private && !data.passkeys.is_valid(&key) {
+            let response = ErrorResponse {
+                transaction_id: request.transaction_id,
+                message: "unauthorized".to_string()
+            };
+
+            return (response.into(), src);
+        }
+
+        let info_hashes = truncate_scrape_info_hashes(
+            &request.info_hashes,
+            config.network.max_scrape_torrents,
+        );
+
         let mut stats: Vec<TorrentScrapeStatistics> = Vec::with_capacity(
-            request.info_hashes.len()
+            info_hashes.len()
         );
 
-        for info_hash in request.info_hashes.iter() {
+        for info_hash in info_hashes.iter() {
             if let Some(torrent_data) = data.torrents.get(info_hash){
                 stats.push(create_torrent_scrape_statistics(
                     torrent_data.num_seeders.load(Ordering::SeqCst) as i32,
                     torrent_data.num_leechers.load(Ordering::SeqCst) as i32,
+                    torrent_data.num_downloads.load(Ordering::SeqCst) as i32,
                 ));
             } else {
                 stats.push(empty_stats);
@@ -328,14 +409,28 @@ pub fn extract_response_peers(
 }
 
 
+/// A download is only counted once, at the point a peer transitions
+/// into seeding via the Completed event. A peer that is already
+/// seeding and re-announces Completed (e.g. a buggy client) must not
+/// inflate the counter.
+#[inline(always)]
+fn counts_as_new_download(
+    event: AnnounceEvent,
+    opt_removed_peer_status: Option<PeerStatus>,
+) -> bool {
+    event == AnnounceEvent::Completed && opt_removed_peer_status != Some(PeerStatus::Seeding)
+}
+
+
 #[inline(always)]
 pub fn create_torrent_scrape_statistics(
     seeders: i32,
-    leechers: i32
+    leechers: i32,
+    downloads: i32,
 ) -> TorrentScrapeStatistics {
     TorrentScrapeStatistics {
         seeders: NumberOfPeers(seeders),
-        completed: NumberOfDownloads(0), // No implementation planned
+        completed: NumberOfDownloads(downloads),
         leechers: NumberOfPeers(leechers)
     }
 }
@@ -420,4 +515,48 @@ mod tests {
 
         quickcheck(prop as fn((u32, u16)) -> TestResult);
     }
+
+    #[test]
+    fn test_counts_as_new_download(){
+        // A fresh peer (no prior entry) completing its download counts.
+        assert!(counts_as_new_download(AnnounceEvent::Completed, None));
+
+        // A leecher transitioning to Completed counts.
+        assert!(counts_as_new_download(
+            AnnounceEvent::Completed,
+            Some(PeerStatus::Leeching)
+        ));
+
+        // A peer that was already seeding and re-announces Completed
+        // (e.g. a buggy client) must not inflate the counter.
+        assert!(!counts_as_new_download(
+            AnnounceEvent::Completed,
+            Some(PeerStatus::Seeding)
+        ));
+
+        // Any other event never counts, regardless of prior status.
+        assert!(!counts_as_new_download(AnnounceEvent::Started, None));
+        assert!(!counts_as_new_download(
+            AnnounceEvent::Stopped,
+            Some(PeerStatus::Leeching)
+        ));
+    }
+
+    #[test]
+    fn test_truncate_scrape_info_hashes(){
+        let info_hashes: Vec<InfoHash> = (0..5u8)
+            .map(|i| InfoHash([i; 20]))
+            .collect();
+
+        // Under the cap: nothing is dropped.
+        assert_eq!(truncate_scrape_info_hashes(&info_hashes, 10).len(), 5);
+
+        // At the cap exactly: nothing is dropped.
+        assert_eq!(truncate_scrape_info_hashes(&info_hashes, 5).len(), 5);
+
+        // Over the cap: truncated to the cap, keeping the leading entries.
+        let truncated = truncate_scrape_info_hashes(&info_hashes, 2);
+
+        assert_eq!(truncated, &info_hashes[..2]);
+    }
 }
\ No newline at end of file
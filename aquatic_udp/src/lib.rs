@@ -0,0 +1,54 @@
+pub mod auth;
+pub mod persistence;
+pub mod prometheus;
+pub mod statistics;
+pub mod tasks;
+
+use std::thread;
+use std::time::Duration;
+
+use crate::common::State;
+use crate::config::Config;
+use crate::statistics::StatisticsCollector;
+
+pub const APP_NAME: &str = "aquatic_udp";
+pub const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Entry point called from `main`.
+///
+/// Restores any persisted swarm state before anything else touches
+/// `state.torrents`, spawns the periodic cleanup/persistence task and
+/// (if enabled) the Prometheus exporter, then loops printing statistics
+/// to stdout. Request worker spawning (connect/announce/scrape socket
+/// handling) isn't part of this module.
+pub fn run(config: Config) {
+    let state = State::default();
+
+    persistence::restore_torrents(&config, &state);
+
+    {
+        let config = config.clone();
+        let state = state.clone();
+
+        thread::spawn(move || loop {
+            thread::sleep(Duration::from_secs(config.cleanup_interval));
+
+            tasks::clean_connections_and_torrents(&config, &state);
+        });
+    }
+
+    if config.statistics.prometheus.enabled {
+        let config = config.clone();
+        let state = state.clone();
+
+        thread::spawn(move || prometheus::run_server(config, state));
+    }
+
+    let mut collector = StatisticsCollector::new();
+
+    loop {
+        thread::sleep(Duration::from_secs(config.statistics.interval));
+
+        tasks::gather_and_print_statistics(&mut collector, &state);
+    }
+}
@@ -0,0 +1,401 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::common::*;
+use crate::config::Config;
+
+/// Serializable mirror of [`PeerStatus`]. Kept separate rather than
+/// deriving `Serialize`/`Deserialize` on `PeerStatus` itself, since that
+/// type lives outside this module.
+///
+/// A peer is only ever stored in `TorrentData::peers` while it's seeding
+/// or leeching (a `Stopped` announce removes the peer instead of
+/// inserting it), so there's no `Stopped` variant to round-trip here.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+enum PersistentPeerStatus {
+    Seeding,
+    Leeching,
+}
+
+impl From<PeerStatus> for PersistentPeerStatus {
+    fn from(status: PeerStatus) -> Self {
+        match status {
+            PeerStatus::Seeding => Self::Seeding,
+            PeerStatus::Leeching | PeerStatus::Stopped => Self::Leeching,
+        }
+    }
+}
+
+impl From<PersistentPeerStatus> for PeerStatus {
+    fn from(status: PersistentPeerStatus) -> Self {
+        match status {
+            PersistentPeerStatus::Seeding => Self::Seeding,
+            PersistentPeerStatus::Leeching => Self::Leeching,
+        }
+    }
+}
+
+/// Extends the existing [`Ip`] trait with the bits persistence needs: a
+/// fixed-size, serializable address representation, so `TorrentData<I>`
+/// can be saved and restored with one generic function instead of a
+/// hand-written copy per address family.
+trait PersistableIp: Ip {
+    type Octets: Copy + Serialize + for<'de> Deserialize<'de>;
+
+    fn to_octets(self) -> Self::Octets;
+    fn from_octets(octets: Self::Octets) -> Self;
+}
+
+impl PersistableIp for Ipv4Addr {
+    type Octets = [u8; 4];
+
+    fn to_octets(self) -> Self::Octets {
+        self.octets()
+    }
+
+    fn from_octets(octets: Self::Octets) -> Self {
+        Ipv4Addr::from(octets)
+    }
+}
+
+impl PersistableIp for Ipv6Addr {
+    type Octets = [u8; 16];
+
+    fn to_octets(self) -> Self::Octets {
+        self.octets()
+    }
+
+    fn from_octets(octets: Self::Octets) -> Self {
+        Ipv6Addr::from(octets)
+    }
+}
+
+/// On-disk representation of a single peer.
+///
+/// `Instant` isn't meaningful across a restart, so peers are stored with
+/// the number of seconds remaining until expiry (relative to the time
+/// the snapshot was taken) rather than `valid_until` itself.
+#[derive(Serialize, Deserialize)]
+struct PersistentPeer<A> {
+    ip: A,
+    port: u16,
+    peer_id: [u8; 20],
+    status: PersistentPeerStatus,
+    valid_for_secs: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PersistentTorrent<A> {
+    info_hash: [u8; 20],
+    num_seeders: usize,
+    num_leechers: usize,
+    num_downloads: usize,
+    peers: Vec<PersistentPeer<A>>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct PersistentState {
+    /// Wall-clock time the snapshot was taken, as seconds since
+    /// `UNIX_EPOCH`. `Instant` can't be persisted (it's not meaningful
+    /// across a restart), so this is what lets `restore_torrents` work
+    /// out how long the tracker was actually down.
+    saved_at_unix_secs: u64,
+    ipv4: Vec<PersistentTorrent<[u8; 4]>>,
+    ipv6: Vec<PersistentTorrent<[u8; 16]>>,
+}
+
+fn to_persistent_torrent<I: PersistableIp>(
+    info_hash: &InfoHash,
+    torrent: &TorrentData<I>,
+) -> PersistentTorrent<I::Octets> {
+    let now = Instant::now();
+
+    PersistentTorrent {
+        info_hash: info_hash.0,
+        num_seeders: torrent.num_seeders,
+        num_leechers: torrent.num_leechers,
+        num_downloads: torrent.num_downloads,
+        peers: torrent
+            .peers
+            .iter()
+            .map(|(key, peer)| PersistentPeer {
+                ip: key.ip.to_octets(),
+                port: peer.port.0,
+                peer_id: peer.peer_id.0,
+                status: peer.status.into(),
+                valid_for_secs: peer.valid_until.0.saturating_duration_since(now).as_secs(),
+            })
+            .collect(),
+    }
+}
+
+/// Reconstructs a `TorrentData<I>` from a snapshot taken `elapsed_secs`
+/// of wall-clock time ago. Peers whose `valid_for_secs` (their lease
+/// remaining at save time) doesn't cover that elapsed downtime are
+/// dropped here rather than loaded with a fresh lease and immediately
+/// swept by the next cleanup pass, so counts are correct from the very
+/// first statistics read after startup.
+fn from_persistent_torrent<I: PersistableIp>(
+    persistent_torrent: PersistentTorrent<I::Octets>,
+    now: Instant,
+    elapsed_secs: u64,
+) -> (InfoHash, TorrentData<I>) {
+    let mut torrent = TorrentData::<I>::default();
+
+    torrent.num_seeders = persistent_torrent.num_seeders;
+    torrent.num_leechers = persistent_torrent.num_leechers;
+    torrent.num_downloads = persistent_torrent.num_downloads;
+
+    for persistent_peer in persistent_torrent.peers {
+        let remaining_secs = persistent_peer.valid_for_secs.saturating_sub(elapsed_secs);
+
+        if remaining_secs == 0 {
+            match persistent_peer.status {
+                PersistentPeerStatus::Seeding => {
+                    torrent.num_seeders = torrent.num_seeders.saturating_sub(1);
+                }
+                PersistentPeerStatus::Leeching => {
+                    torrent.num_leechers = torrent.num_leechers.saturating_sub(1);
+                }
+            }
+
+            continue;
+        }
+
+        let valid_until = now + Duration::from_secs(remaining_secs);
+        let ip = I::from_octets(persistent_peer.ip);
+
+        torrent.peers.insert(
+            PeerMapKey {
+                ip,
+                peer_id: PeerId(persistent_peer.peer_id),
+            },
+            Peer {
+                ip_address: ip,
+                port: Port(persistent_peer.port),
+                status: persistent_peer.status.into(),
+                valid_until: Time(valid_until),
+            },
+        );
+    }
+
+    (InfoHash(persistent_torrent.info_hash), torrent)
+}
+
+/// Serialize `state.torrents` to `config.persistence.db_path` in bincode
+/// format. Called by [`clean_connections_and_torrents`] as its last step,
+/// on the same interval, so snapshots are always taken right after expired
+/// peers have been swept out.
+///
+/// [`clean_connections_and_torrents`]: crate::tasks::clean_connections_and_torrents
+pub fn save_torrents(config: &Config, state: &State) {
+    if !config.persistence.enabled {
+        return;
+    }
+
+    let saved_at_unix_secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    let persistent_state = {
+        let torrents = state.torrents.lock();
+
+        PersistentState {
+            saved_at_unix_secs,
+            ipv4: torrents
+                .ipv4
+                .iter()
+                .map(|(info_hash, torrent)| to_persistent_torrent(info_hash, torrent))
+                .collect(),
+            ipv6: torrents
+                .ipv6
+                .iter()
+                .map(|(info_hash, torrent)| to_persistent_torrent(info_hash, torrent))
+                .collect(),
+        }
+    };
+
+    let result = File::create(&config.persistence.db_path)
+        .map(BufWriter::new)
+        .and_then(|writer| {
+            bincode::serialize_into(writer, &persistent_state)
+                .map_err(|err| std::io::Error::new(ErrorKind::Other, err))
+        });
+
+    if let Err(err) = result {
+        ::log::error!(
+            "Error persisting torrents to {:?}: {}",
+            config.persistence.db_path,
+            err
+        );
+    }
+}
+
+/// Load a snapshot written by [`save_torrents`] into `state.torrents`.
+/// Called once at startup, before request workers are spawned, so counts
+/// and long-lived peers come back instead of starting at zero.
+pub fn restore_torrents(config: &Config, state: &State) {
+    if !config.persistence.enabled || !Path::new(&config.persistence.db_path).exists() {
+        return;
+    }
+
+    let persistent_state: PersistentState = match File::open(&config.persistence.db_path)
+        .map(BufReader::new)
+        .and_then(|reader| {
+            bincode::deserialize_from(reader).map_err(|err| std::io::Error::new(ErrorKind::Other, err))
+        }) {
+        Ok(persistent_state) => persistent_state,
+        Err(err) => {
+            ::log::error!(
+                "Error restoring torrents from {:?}: {}",
+                config.persistence.db_path,
+                err
+            );
+
+            return;
+        }
+    };
+
+    let now_unix_secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let elapsed_secs = now_unix_secs.saturating_sub(persistent_state.saved_at_unix_secs);
+
+    let now = Instant::now();
+    let mut torrents = state.torrents.lock();
+
+    for persistent_torrent in persistent_state.ipv4 {
+        let (info_hash, torrent) =
+            from_persistent_torrent::<Ipv4Addr>(persistent_torrent, now, elapsed_secs);
+
+        torrents.ipv4.insert(info_hash, torrent);
+    }
+
+    for persistent_torrent in persistent_state.ipv6 {
+        let (info_hash, torrent) =
+            from_persistent_torrent::<Ipv6Addr>(persistent_torrent, now, elapsed_secs);
+
+        torrents.ipv6.insert(info_hash, torrent);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn gen_torrent_with_one_peer(status: PeerStatus) -> TorrentData<Ipv4Addr> {
+        let ip = Ipv4Addr::from([127, 0, 0, 1]);
+        let peer_id = PeerId([0; 20]);
+
+        let mut torrent = TorrentData::<Ipv4Addr>::default();
+
+        torrent.num_seeders = if status == PeerStatus::Seeding { 1 } else { 0 };
+        torrent.num_leechers = if status == PeerStatus::Leeching { 1 } else { 0 };
+        torrent.num_downloads = 3;
+
+        torrent.peers.insert(
+            PeerMapKey { ip, peer_id },
+            Peer {
+                ip_address: ip,
+                port: Port(1),
+                status,
+                valid_until: Time(Instant::now() + Duration::from_secs(60)),
+            },
+        );
+
+        torrent
+    }
+
+    #[test]
+    fn test_persistent_torrent_round_trip_preserves_peer_status() {
+        for status in [PeerStatus::Seeding, PeerStatus::Leeching] {
+            let info_hash = InfoHash([1; 20]);
+            let torrent = gen_torrent_with_one_peer(status);
+
+            let persistent_torrent = to_persistent_torrent(&info_hash, &torrent);
+            let (restored_info_hash, restored_torrent) =
+                from_persistent_torrent::<Ipv4Addr>(persistent_torrent, Instant::now(), 0);
+
+            assert_eq!(restored_info_hash, info_hash);
+            assert_eq!(restored_torrent.num_seeders, torrent.num_seeders);
+            assert_eq!(restored_torrent.num_leechers, torrent.num_leechers);
+            assert_eq!(restored_torrent.num_downloads, torrent.num_downloads);
+
+            let restored_peer = restored_torrent
+                .peers
+                .values()
+                .next()
+                .expect("restored peer");
+
+            assert_eq!(restored_peer.status, status);
+        }
+    }
+
+    #[test]
+    fn test_persistent_torrent_round_trip_drops_peers_already_expired_at_save_time() {
+        let info_hash = InfoHash([2; 20]);
+        let mut torrent = gen_torrent_with_one_peer(PeerStatus::Seeding);
+
+        for peer in torrent.peers.values_mut() {
+            peer.valid_until = Time(Instant::now());
+        }
+
+        let persistent_torrent = to_persistent_torrent(&info_hash, &torrent);
+        let (_, restored_torrent) = from_persistent_torrent::<Ipv4Addr>(
+            persistent_torrent,
+            Instant::now() + Duration::from_secs(1),
+            0,
+        );
+
+        assert!(restored_torrent.peers.is_empty());
+    }
+
+    #[test]
+    fn test_persistent_torrent_round_trip_drops_peers_that_expired_during_downtime() {
+        let info_hash = InfoHash([3; 20]);
+        let mut torrent = gen_torrent_with_one_peer(PeerStatus::Seeding);
+
+        // 10 seconds left at save time.
+        for peer in torrent.peers.values_mut() {
+            peer.valid_until = Time(Instant::now() + Duration::from_secs(10));
+        }
+
+        let persistent_torrent = to_persistent_torrent(&info_hash, &torrent);
+
+        // The tracker was down for an hour: with no wall-clock anchor,
+        // a restart-relative restore would re-arm this peer with a
+        // fresh 10s lease instead of dropping it as long expired.
+        let (_, restored_torrent) =
+            from_persistent_torrent::<Ipv4Addr>(persistent_torrent, Instant::now(), 3600);
+
+        assert!(restored_torrent.peers.is_empty());
+    }
+
+    #[test]
+    fn test_persistent_torrent_round_trip_keeps_peers_that_survive_downtime() {
+        let info_hash = InfoHash([4; 20]);
+        let mut torrent = gen_torrent_with_one_peer(PeerStatus::Seeding);
+
+        // 60 seconds left at save time.
+        for peer in torrent.peers.values_mut() {
+            peer.valid_until = Time(Instant::now() + Duration::from_secs(60));
+        }
+
+        let persistent_torrent = to_persistent_torrent(&info_hash, &torrent);
+
+        // Only down for 10s: 50s of lease should remain.
+        let (_, restored_torrent) =
+            from_persistent_torrent::<Ipv4Addr>(persistent_torrent, Instant::now(), 10);
+
+        assert_eq!(restored_torrent.peers.len(), 1);
+    }
+}
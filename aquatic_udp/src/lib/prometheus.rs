@@ -0,0 +1,131 @@
+use std::io::Write;
+use std::net::TcpListener;
+
+use crate::common::*;
+use crate::config::Config;
+use crate::statistics::StatisticsCollector;
+
+/// Fixed `le` boundaries for the `aquatic_peers_per_torrent` histogram.
+///
+/// Counts toward each bucket are taken fresh from the raw per-torrent
+/// peer counts on every scrape, so they stay both cumulative and
+/// monotonic across boundaries, and the boundaries themselves never
+/// shift between scrapes the way percentile-derived ones would — both
+/// are required for `histogram_quantile()`/`rate()` to work correctly.
+const PEERS_PER_TORRENT_BUCKETS: [u64; 11] = [0, 1, 2, 4, 8, 16, 32, 64, 128, 256, 512];
+
+/// Serve `state`'s statistics in Prometheus text exposition format.
+///
+/// Blocks accepting connections on `config.statistics.prometheus.address`
+/// for as long as the tracker runs; meant to be spawned on its own
+/// thread, the same way [`crate::handlers::run_request_worker`] and the
+/// cleanup loop are. Does nothing if the feature isn't enabled in the
+/// config.
+pub fn run_server(config: Config, state: State) {
+    if !config.statistics.prometheus.enabled {
+        return;
+    }
+
+    let listener = match TcpListener::bind(config.statistics.prometheus.address) {
+        Ok(listener) => listener,
+        Err(err) => {
+            ::log::error!("Could not bind prometheus exporter socket: {}", err);
+
+            return;
+        }
+    };
+
+    let mut collector = StatisticsCollector::new();
+
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                ::log::error!("Error accepting prometheus exporter connection: {}", err);
+
+                continue;
+            }
+        };
+
+        let body = render_metrics(&mut collector, &state);
+
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+
+        if let Err(err) = stream.write_all(response.as_bytes()) {
+            ::log::error!("Error writing prometheus exporter response: {}", err);
+        }
+    }
+}
+
+fn render_metrics(collector: &mut StatisticsCollector, state: &State) -> String {
+    let snapshot = collector.collect(state);
+
+    let mut peers_per_torrent: Vec<u64> = Vec::new();
+
+    let num_torrents = {
+        let torrents = state.torrents.lock();
+
+        for torrent in torrents.ipv4.values() {
+            peers_per_torrent.push((torrent.num_seeders + torrent.num_leechers) as u64);
+        }
+        for torrent in torrents.ipv6.values() {
+            peers_per_torrent.push((torrent.num_seeders + torrent.num_leechers) as u64);
+        }
+
+        torrents.ipv4.len() + torrents.ipv6.len()
+    };
+
+    let mut out = String::new();
+
+    out.push_str("# TYPE aquatic_requests_received_total counter\n");
+    out.push_str(&format!(
+        "aquatic_requests_received_total {}\n",
+        snapshot.requests_received_total
+    ));
+    out.push_str("# TYPE aquatic_responses_sent_total counter\n");
+    out.push_str(&format!(
+        "aquatic_responses_sent_total {}\n",
+        snapshot.responses_sent_total
+    ));
+    out.push_str("# TYPE aquatic_bytes_received_total counter\n");
+    out.push_str(&format!(
+        "aquatic_bytes_received_total {}\n",
+        snapshot.bytes_received_total
+    ));
+    out.push_str("# TYPE aquatic_bytes_sent_total counter\n");
+    out.push_str(&format!("aquatic_bytes_sent_total {}\n", snapshot.bytes_sent_total));
+
+    out.push_str("# TYPE aquatic_torrents gauge\n");
+    out.push_str(&format!("aquatic_torrents {}\n", num_torrents));
+
+    out.push_str("# TYPE aquatic_peers_per_torrent histogram\n");
+
+    if !peers_per_torrent.is_empty() {
+        for &bucket_max in &PEERS_PER_TORRENT_BUCKETS {
+            let bucket_count = peers_per_torrent.iter().filter(|&&n| n <= bucket_max).count();
+
+            out.push_str(&format!(
+                "aquatic_peers_per_torrent_bucket{{le=\"{}\"}} {}\n",
+                bucket_max, bucket_count
+            ));
+        }
+
+        let sum: u64 = peers_per_torrent.iter().sum();
+
+        out.push_str(&format!(
+            "aquatic_peers_per_torrent_bucket{{le=\"+Inf\"}} {}\n",
+            peers_per_torrent.len()
+        ));
+        out.push_str(&format!("aquatic_peers_per_torrent_sum {}\n", sum));
+        out.push_str(&format!(
+            "aquatic_peers_per_torrent_count {}\n",
+            peers_per_torrent.len()
+        ));
+    }
+
+    out
+}
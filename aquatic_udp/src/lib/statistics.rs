@@ -0,0 +1,98 @@
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+
+use crate::common::*;
+
+/// A point-in-time view of the statistics counters.
+///
+/// Unlike the old `fetch_and(0, ..)` approach, the underlying atomics in
+/// `state.statistics` are never reset here: they keep counting up for as
+/// long as the process runs, which is what Prometheus expects from a
+/// counter. Per-second rates are derived by diffing against the previous
+/// [`StatisticsCollector`] reading instead.
+pub struct StatisticsSnapshot {
+    pub requests_received_total: usize,
+    pub responses_sent_total: usize,
+    pub bytes_received_total: usize,
+    pub bytes_sent_total: usize,
+    pub readable_events_total: usize,
+
+    pub requests_per_second: f64,
+    pub responses_per_second: f64,
+    pub bytes_received_per_second: f64,
+    pub bytes_sent_per_second: f64,
+    pub requests_per_readable_event: f64,
+}
+
+/// Keeps the previous totals and the instant they were read at, so both
+/// the stdout printer and the Prometheus exporter can compute rates
+/// without destroying the underlying counters on read.
+pub struct StatisticsCollector {
+    prev_requests_received: usize,
+    prev_responses_sent: usize,
+    prev_bytes_received: usize,
+    prev_bytes_sent: usize,
+    prev_readable_events: usize,
+    prev_instant: Instant,
+}
+
+impl StatisticsCollector {
+    pub fn new() -> Self {
+        Self {
+            prev_requests_received: 0,
+            prev_responses_sent: 0,
+            prev_bytes_received: 0,
+            prev_bytes_sent: 0,
+            prev_readable_events: 0,
+            prev_instant: Instant::now(),
+        }
+    }
+
+    pub fn collect(&mut self, state: &State) -> StatisticsSnapshot {
+        let requests_received_total = state.statistics.requests_received.load(Ordering::SeqCst);
+        let responses_sent_total = state.statistics.responses_sent.load(Ordering::SeqCst);
+        let bytes_received_total = state.statistics.bytes_received.load(Ordering::SeqCst);
+        let bytes_sent_total = state.statistics.bytes_sent.load(Ordering::SeqCst);
+        let readable_events_total = state.statistics.readable_events.load(Ordering::SeqCst);
+
+        let now = Instant::now();
+        let elapsed = (now - self.prev_instant).as_secs_f64().max(f64::EPSILON);
+
+        let requests_per_second =
+            (requests_received_total - self.prev_requests_received) as f64 / elapsed;
+        let responses_per_second =
+            (responses_sent_total - self.prev_responses_sent) as f64 / elapsed;
+        let bytes_received_per_second =
+            (bytes_received_total - self.prev_bytes_received) as f64 / elapsed;
+        let bytes_sent_per_second = (bytes_sent_total - self.prev_bytes_sent) as f64 / elapsed;
+
+        let readable_events_delta = readable_events_total - self.prev_readable_events;
+
+        let requests_per_readable_event = if readable_events_delta == 0 {
+            0.0
+        } else {
+            (requests_received_total - self.prev_requests_received) as f64
+                / readable_events_delta as f64
+        };
+
+        self.prev_requests_received = requests_received_total;
+        self.prev_responses_sent = responses_sent_total;
+        self.prev_bytes_received = bytes_received_total;
+        self.prev_bytes_sent = bytes_sent_total;
+        self.prev_readable_events = readable_events_total;
+        self.prev_instant = now;
+
+        StatisticsSnapshot {
+            requests_received_total,
+            responses_sent_total,
+            bytes_received_total,
+            bytes_sent_total,
+            readable_events_total,
+            requests_per_second,
+            responses_per_second,
+            bytes_received_per_second,
+            bytes_sent_per_second,
+            requests_per_readable_event,
+        }
+    }
+}
@@ -1,4 +1,3 @@
-use std::sync::atomic::Ordering;
 use std::time::Instant;
 
 use histogram::Histogram;
@@ -6,7 +5,9 @@ use histogram::Histogram;
 use aquatic_common::AccessListType;
 
 use crate::common::*;
-use crate::config::Config;
+use crate::config::{Config, TrackerMode};
+use crate::persistence;
+use crate::statistics::StatisticsCollector;
 
 pub fn clean_connections_and_torrents(config: &Config, state: &State) {
     let now = Instant::now();
@@ -54,9 +55,24 @@ pub fn clean_connections_and_torrents(config: &Config, state: &State) {
             torrents.ipv6.shrink_to_fit();
         }
     }
+
+    if config.mode == TrackerMode::Private {
+        let mut passkeys = state.passkeys.lock();
+
+        if let Err(err) = passkeys.update_from_path(&config.auth.passkeys_path) {
+            ::log::error!("Update passkeys from path: {:?}", err);
+        }
+    }
+
+    persistence::save_torrents(config, state);
 }
 
 /// Returns true if torrent is to be kept
+///
+/// Only drops expired peers and adjusts seeder/leecher counts accordingly.
+/// `num_downloads` is left untouched here, since a peer expiring doesn't
+/// undo a completed download; it's only reset when the whole torrent
+/// entry above is dropped from the map.
 #[inline]
 fn clean_torrent_and_peers<I: Ip>(now: Instant, torrent: &mut TorrentData<I>) -> bool {
     let num_seeders = &mut torrent.num_seeders;
@@ -83,47 +99,20 @@ fn clean_torrent_and_peers<I: Ip>(now: Instant, torrent: &mut TorrentData<I>) ->
     !torrent.peers.is_empty()
 }
 
-pub fn gather_and_print_statistics(state: &State, config: &Config) {
-    let interval = config.statistics.interval;
-
-    let requests_received: f64 = state
-        .statistics
-        .requests_received
-        .fetch_and(0, Ordering::SeqCst) as f64;
-    let responses_sent: f64 = state
-        .statistics
-        .responses_sent
-        .fetch_and(0, Ordering::SeqCst) as f64;
-    let bytes_received: f64 = state
-        .statistics
-        .bytes_received
-        .fetch_and(0, Ordering::SeqCst) as f64;
-    let bytes_sent: f64 = state.statistics.bytes_sent.fetch_and(0, Ordering::SeqCst) as f64;
-
-    let requests_per_second = requests_received / interval as f64;
-    let responses_per_second: f64 = responses_sent / interval as f64;
-    let bytes_received_per_second: f64 = bytes_received / interval as f64;
-    let bytes_sent_per_second: f64 = bytes_sent / interval as f64;
-
-    let readable_events: f64 = state
-        .statistics
-        .readable_events
-        .fetch_and(0, Ordering::SeqCst) as f64;
-    let requests_per_readable_event = if readable_events == 0.0 {
-        0.0
-    } else {
-        requests_received / readable_events
-    };
+pub fn gather_and_print_statistics(collector: &mut StatisticsCollector, state: &State) {
+    let snapshot = collector.collect(state);
 
     println!(
         "stats: {:.2} requests/second, {:.2} responses/second, {:.2} requests/readable event",
-        requests_per_second, responses_per_second, requests_per_readable_event
+        snapshot.requests_per_second,
+        snapshot.responses_per_second,
+        snapshot.requests_per_readable_event
     );
 
     println!(
         "bandwidth: {:7.2} Mbit/s in, {:7.2} Mbit/s out",
-        bytes_received_per_second * 8.0 / 1_000_000.0,
-        bytes_sent_per_second * 8.0 / 1_000_000.0,
+        snapshot.bytes_received_per_second * 8.0 / 1_000_000.0,
+        snapshot.bytes_sent_per_second * 8.0 / 1_000_000.0,
     );
 
     let mut peers_per_torrent = Histogram::new();